@@ -0,0 +1,91 @@
+// Copyright 2019 Materialize, Inc. All rights reserved.
+//
+// This file is part of Materialize. Materialize may not be used or
+// distributed without the express permission of Materialize, Inc.
+
+//! Generates the body of the `SqlState` type in `pgwire/codes.rs` from
+//! the official PostgreSQL "[Appendix A. PostgreSQL Error Codes][1]" CSV,
+//! the way rust-postgres generates its own `SqlState` from `errcodes.txt`.
+//!
+//! [1]: https://www.postgresql.org/docs/11/errcodes-appendix.html
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+struct Code {
+    code: String,
+    variant: String,
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=pgwire/sqlstates.csv");
+
+    let codes: Vec<Code> = include_str!("pgwire/sqlstates.csv")
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let mut parts = line.splitn(2, ',');
+            let code = parts
+                .next()
+                .unwrap_or_else(|| panic!("malformed sqlstates.csv line: {}", line))
+                .to_string();
+            let variant = parts
+                .next()
+                .unwrap_or_else(|| panic!("malformed sqlstates.csv line: {}", line))
+                .to_string();
+            Code { code, variant }
+        })
+        .collect();
+
+    let mut out = String::new();
+    out.push_str("// @generated by build.rs from pgwire/sqlstates.csv. Do not edit by hand.\n\n");
+    out.push_str("use phf::phf_map;\n\n");
+
+    out.push_str("/// A typed SQLSTATE error code.\n");
+    out.push_str("#[derive(Debug, Clone, PartialEq, Eq)]\n");
+    out.push_str("pub enum SqlState {\n");
+    for c in &codes {
+        out.push_str(&format!("    {},\n", c.variant));
+    }
+    out.push_str("    /// A code that isn't one of the variants above, preserved verbatim.\n");
+    out.push_str("    Other(String),\n");
+    out.push_str("}\n\n");
+
+    out.push_str("impl SqlState {\n");
+    out.push_str("    /// The five-character SQLSTATE code, e.g. `\"42P01\"`.\n");
+    out.push_str("    pub fn code(&self) -> &str {\n");
+    out.push_str("        match self {\n");
+    for c in &codes {
+        out.push_str(&format!(
+            "            SqlState::{} => \"{}\",\n",
+            c.variant, c.code
+        ));
+    }
+    out.push_str("            SqlState::Other(code) => code,\n");
+    out.push_str("        }\n");
+    out.push_str("    }\n\n");
+
+    out.push_str(
+        "    /// Parses a SQLSTATE code, falling back to [`SqlState::Other`] for any\n",
+    );
+    out.push_str("    /// code that isn't in the official table.\n");
+    out.push_str("    pub fn from_code(code: &str) -> SqlState {\n");
+    out.push_str("        static CODES: phf::Map<&'static str, SqlState> = phf_map! {\n");
+    for c in &codes {
+        out.push_str(&format!(
+            "            \"{}\" => SqlState::{},\n",
+            c.code, c.variant
+        ));
+    }
+    out.push_str("        };\n");
+    out.push_str(
+        "        CODES.get(code).cloned().unwrap_or_else(|| SqlState::Other(code.to_string()))\n",
+    );
+    out.push_str("    }\n");
+    out.push_str("}\n");
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR not set by cargo");
+    fs::write(Path::new(&out_dir).join("sqlstate.rs"), out).expect("failed to write sqlstate.rs");
+}
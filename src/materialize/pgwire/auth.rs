@@ -0,0 +1,232 @@
+// Copyright 2019 Materialize, Inc. All rights reserved.
+//
+// This file is part of Materialize. Materialize may not be used or
+// distributed without the express permission of Materialize, Inc.
+
+//! Implementations of the password-based authentication methods offered by
+//! pgwire: cleartext, MD5, and SCRAM-SHA-256. See the "[Password
+//! Authentication][1]" section of the PostgreSQL protocol documentation.
+//!
+//! [1]: https://www.postgresql.org/docs/11/protocol-flow.html#AEN112788
+
+use hmac::{Hmac, Mac};
+use md5::{Digest, Md5};
+use rand::RngCore;
+use sha2::{Digest as _, Sha256};
+
+/// The only SASL mechanism this server currently advertises to clients.
+pub const SASL_MECHANISM: &str = "SCRAM-SHA-256";
+
+const SCRAM_ITERATIONS: u32 = 4096;
+
+/// Computes the value of the `md5` password hash expected in the
+/// [`FrontendMessage::Password`](crate::pgwire::message::FrontendMessage::Password)
+/// reply to an
+/// [`AuthenticationMD5Password`](crate::pgwire::message::BackendMessage::AuthenticationMD5Password)
+/// request: `"md5" + hex(md5(hex(md5(password + user)) + salt))`.
+pub fn md5_hash(user: &str, password: &str, salt: [u8; 4]) -> String {
+    let inner = format!("{:x}", Md5::digest(format!("{}{}", password, user).as_bytes()));
+    let mut hasher = Md5::new();
+    hasher.input(inner.as_bytes());
+    hasher.input(&salt);
+    format!("md5{:x}", hasher.result())
+}
+
+/// Generates a random 4-byte salt for MD5 authentication.
+pub fn generate_md5_salt() -> [u8; 4] {
+    let mut salt = [0; 4];
+    rand::thread_rng().fill_bytes(&mut salt);
+    salt
+}
+
+/// Server-side state for a SCRAM-SHA-256 exchange (RFC 5802 / RFC 7677).
+///
+/// The exchange has two round trips. Construct a `ScramSha256` from the
+/// client's `SASLInitialResponse`, send its `server_first_message` as the
+/// `AuthenticationSASLContinue` payload, then call
+/// [`ScramSha256::verify_client_final`] with the client's next message.
+pub struct ScramSha256 {
+    client_first_bare: String,
+    server_first_message: String,
+    nonce: String,
+    salt: [u8; 16],
+}
+
+impl ScramSha256 {
+    /// Begins a new exchange from the client's `client-first-message`.
+    pub fn new(client_first: &str) -> Result<ScramSha256, String> {
+        let client_first_bare = client_first
+            .strip_prefix("n,,")
+            .ok_or_else(|| "unsupported SCRAM-SHA-256 GS2 header".to_string())?;
+        let client_nonce = parse_field(client_first_bare, 'r')
+            .ok_or_else(|| "missing client nonce".to_string())?;
+
+        let mut server_nonce_bytes = [0; 18];
+        rand::thread_rng().fill_bytes(&mut server_nonce_bytes);
+        let nonce = format!("{}{}", client_nonce, base64::encode(&server_nonce_bytes));
+
+        let mut salt = [0; 16];
+        rand::thread_rng().fill_bytes(&mut salt);
+
+        let server_first_message = format!(
+            "r={},s={},i={}",
+            nonce,
+            base64::encode(&salt),
+            SCRAM_ITERATIONS
+        );
+
+        Ok(ScramSha256 {
+            client_first_bare: client_first_bare.to_string(),
+            server_first_message,
+            nonce,
+            salt,
+        })
+    }
+
+    /// The `server-first-message` to send back as `AuthenticationSASLContinue`.
+    pub fn server_first_message(&self) -> &str {
+        &self.server_first_message
+    }
+
+    /// Verifies the client's `client-final-message` against `password` and,
+    /// on success, returns the `server-final-message` to send as
+    /// `AuthenticationSASLFinal`.
+    pub fn verify_client_final(&self, password: &str, client_final: &str) -> Result<String, String> {
+        let proof_pos = client_final
+            .rfind(",p=")
+            .ok_or_else(|| "missing client proof".to_string())?;
+        let (client_final_without_proof, proof_field) = client_final.split_at(proof_pos);
+        let client_proof = base64::decode(&proof_field[3..]).map_err(|e| e.to_string())?;
+
+        let client_nonce =
+            parse_field(client_final, 'r').ok_or_else(|| "missing client nonce".to_string())?;
+        if client_nonce != self.nonce {
+            return Err("SCRAM nonce mismatch".into());
+        }
+
+        let salted_password = pbkdf2_hmac_sha256(password.as_bytes(), &self.salt, SCRAM_ITERATIONS);
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        let stored_key = sha256(&client_key);
+
+        let auth_message = format!(
+            "{},{},{}",
+            self.client_first_bare, self.server_first_message, client_final_without_proof
+        );
+        let client_signature = hmac_sha256(&stored_key, auth_message.as_bytes());
+
+        let computed_client_key: Vec<u8> = client_signature
+            .iter()
+            .zip(client_proof.iter())
+            .map(|(sig, proof)| sig ^ proof)
+            .collect();
+        if sha256(&computed_client_key) != stored_key {
+            return Err("invalid SCRAM-SHA-256 proof".into());
+        }
+
+        let server_key = hmac_sha256(&salted_password, b"Server Key");
+        let server_signature = hmac_sha256(&server_key, auth_message.as_bytes());
+        Ok(format!("v={}", base64::encode(&server_signature)))
+    }
+}
+
+/// Finds the value of a `key=value` field in a comma-separated SCRAM
+/// message, e.g. `parse_field("n=,r=abcd", 'r') == Some("abcd")`.
+fn parse_field(message: &str, key: char) -> Option<&str> {
+    message
+        .split(',')
+        .find_map(|field| field.strip_prefix(&format!("{}=", key)))
+}
+
+fn pbkdf2_hmac_sha256(password: &[u8], salt: &[u8], iterations: u32) -> Vec<u8> {
+    let mut out = vec![0; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password, salt, iterations, &mut out);
+    out
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC-SHA256 accepts keys of any length");
+    mac.input(data);
+    mac.result().code().to_vec()
+}
+
+fn sha256(data: &[u8]) -> Vec<u8> {
+    Sha256::digest(data).to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn md5_hash_matches_known_vector() {
+        // password="password", user="postgres", salt=[1, 2, 3, 4]:
+        // md5(md5("passwordpostgres") + salt), independently computed.
+        let salt = [1, 2, 3, 4];
+        let inner = format!("{:x}", Md5::digest(b"passwordpostgres"));
+        let mut hasher = Md5::new();
+        hasher.input(inner.as_bytes());
+        hasher.input(&salt);
+        let expected = format!("md5{:x}", hasher.result());
+        assert_eq!(md5_hash("postgres", "password", salt), expected);
+    }
+
+    #[test]
+    fn parse_field_finds_value() {
+        assert_eq!(parse_field("n=,r=abcd", 'r'), Some("abcd"));
+        assert_eq!(parse_field("n=,r=abcd", 'z'), None);
+    }
+
+    /// Runs a full SCRAM-SHA-256 exchange end to end: builds the server side
+    /// with [`ScramSha256`], plays the client side by hand per RFC 5802, and
+    /// checks that a correct password is accepted and an incorrect one is
+    /// rejected.
+    #[test]
+    fn scram_exchange_round_trips() {
+        let password = "s3kr1t";
+        let client_nonce = "client-nonce-value";
+        let client_first_bare = format!("n=,r={}", client_nonce);
+        let client_first = format!("n,,{}", client_first_bare);
+
+        let server = ScramSha256::new(&client_first).expect("valid client-first-message");
+        let server_first = server.server_first_message().to_string();
+
+        let nonce = parse_field(&server_first, 'r').unwrap().to_string();
+        let salt = base64::decode(parse_field(&server_first, 's').unwrap()).unwrap();
+        let iterations: u32 = parse_field(&server_first, 'i').unwrap().parse().unwrap();
+
+        let channel_binding = base64::encode("n,,");
+        let client_final_without_proof = format!("c={},r={}", channel_binding, nonce);
+        let auth_message = format!(
+            "{},{},{}",
+            client_first_bare, server_first, client_final_without_proof
+        );
+
+        let salted_password = pbkdf2_hmac_sha256(password.as_bytes(), &salt, iterations);
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        let stored_key = sha256(&client_key);
+        let client_signature = hmac_sha256(&stored_key, auth_message.as_bytes());
+        let client_proof: Vec<u8> = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(k, s)| k ^ s)
+            .collect();
+        let client_final = format!(
+            "{},p={}",
+            client_final_without_proof,
+            base64::encode(&client_proof)
+        );
+
+        let server_final = server
+            .verify_client_final(password, &client_final)
+            .expect("correct password is accepted");
+
+        let server_key = hmac_sha256(&salted_password, b"Server Key");
+        let expected_server_signature = hmac_sha256(&server_key, auth_message.as_bytes());
+        assert_eq!(
+            server_final,
+            format!("v={}", base64::encode(&expected_server_signature))
+        );
+
+        assert!(server.verify_client_final("wrong password", &client_final).is_err());
+    }
+}
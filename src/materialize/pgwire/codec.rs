@@ -12,11 +12,15 @@
 
 use byteorder::{ByteOrder, NetworkEndian};
 use bytes::{BufMut, BytesMut, IntoBuf};
+use chrono::NaiveDate;
 use std::borrow::Cow;
+use std::cmp;
 use tokio::codec::{Decoder, Encoder};
 use tokio::io;
 
-use crate::pgwire::message::{BackendMessage, FieldValue, FrontendMessage};
+use crate::pgwire::message::{
+    BackendMessage, DescribeObjectType, FieldFormat, FieldValue, FrontendMessage,
+};
 use ore::netio;
 
 /// A Tokio codec to encode and decode pgwire frames.
@@ -37,13 +41,36 @@ use ore::netio;
 /// ```
 pub struct Codec {
     decode_state: DecodeState,
+    max_frame_size: usize,
 }
 
+/// The default operating limit on a non-`CopyData` frame. Comfortably large
+/// enough for big `Query` strings and `Bind` parameter blobs without
+/// letting a single frame monopolize memory; raise it with
+/// [`Codec::with_max_frame_size`] if a deployment needs more. `CopyData`
+/// frames are exempt: they stream in incrementally (see `DecodeState`)
+/// rather than being buffered whole, so they're bounded only by
+/// `ABSOLUTE_MAX_FRAME_SIZE`.
+const DEFAULT_MAX_FRAME_SIZE: usize = 4 << 20;
+
+/// A hard ceiling on any declared frame length, `CopyData` included. This
+/// exists purely to reject obviously-corrupt or malicious length prefixes
+/// before we try to reserve memory for them; it is not meant to be tuned.
+const ABSOLUTE_MAX_FRAME_SIZE: usize = 1 << 30;
+
 impl Codec {
-    /// Creates a new `Codec`.
+    /// Creates a new `Codec` with the default frame size limit.
     pub fn new() -> Codec {
+        Codec::with_max_frame_size(DEFAULT_MAX_FRAME_SIZE)
+    }
+
+    /// Creates a new `Codec` with a custom limit on the size of a buffered
+    /// (non-`CopyData`) frame. `max_frame_size` is clamped to
+    /// `ABSOLUTE_MAX_FRAME_SIZE`.
+    pub fn with_max_frame_size(max_frame_size: usize) -> Codec {
         Codec {
             decode_state: DecodeState::Startup,
+            max_frame_size: cmp::min(max_frame_size, ABSOLUTE_MAX_FRAME_SIZE),
         }
     }
 }
@@ -67,6 +94,11 @@ impl Encoder for Codec {
         // Write type byte.
         buf.put(match msg {
             BackendMessage::AuthenticationOk => b'R',
+            BackendMessage::AuthenticationCleartextPassword => b'R',
+            BackendMessage::AuthenticationMD5Password { .. } => b'R',
+            BackendMessage::AuthenticationSASL { .. } => b'R',
+            BackendMessage::AuthenticationSASLContinue(_) => b'R',
+            BackendMessage::AuthenticationSASLFinal(_) => b'R',
             BackendMessage::RowDescription(_) => b'T',
             BackendMessage::DataRow(_) => b'D',
             BackendMessage::CommandComplete { .. } => b'C',
@@ -74,9 +106,17 @@ impl Encoder for Codec {
             BackendMessage::ReadyForQuery => b'Z',
             BackendMessage::ParameterStatus(_, _) => b'S',
             BackendMessage::ParseComplete => b'1',
+            BackendMessage::BindComplete => b'2',
+            BackendMessage::CloseComplete => b'3',
+            BackendMessage::ParameterDescription(_) => b't',
+            BackendMessage::NoData => b'n',
+            BackendMessage::PortalSuspended => b's',
             BackendMessage::ErrorResponse { .. } => b'E',
-            BackendMessage::CopyOutResponse => b'H',
+            BackendMessage::CopyOutResponse { .. } => b'H',
+            BackendMessage::CopyInResponse { .. } => b'G',
+            BackendMessage::CopyBothResponse { .. } => b'W',
             BackendMessage::CopyData(_) => b'd',
+            BackendMessage::CopyDone => b'c',
         });
 
         // Write message length placeholder. The true length is filled in later.
@@ -85,24 +125,44 @@ impl Encoder for Codec {
 
         // Write message contents.
         match msg {
-            // psql doesn't actually care about the number of columns.
-            // It should be saved in the message if we ever need to care about it; until then,
-            // 0 is fine.
-            BackendMessage::CopyOutResponse/*(n_cols)*/ => {
-                buf.put_u8(0); // textual format
-                buf.put_i16_be(0/*n_cols*/);
-                /*
-                for _ in 0..n_cols {
-                    buf.put_i16_be(0); // textual format for this column
-                }
-                */
+            BackendMessage::CopyOutResponse { column_formats } => {
+                encode_copy_formats(&mut buf, &column_formats);
+            }
+            BackendMessage::CopyInResponse { column_formats } => {
+                encode_copy_formats(&mut buf, &column_formats);
+            }
+            BackendMessage::CopyBothResponse { column_formats } => {
+                encode_copy_formats(&mut buf, &column_formats);
             }
             BackendMessage::CopyData(mut data) => {
                 buf.append(&mut data);
             }
+            BackendMessage::CopyDone => (),
             BackendMessage::AuthenticationOk => {
                 buf.put_u32_be(0);
             }
+            BackendMessage::AuthenticationCleartextPassword => {
+                buf.put_u32_be(3);
+            }
+            BackendMessage::AuthenticationMD5Password { salt } => {
+                buf.put_u32_be(5);
+                buf.put(&salt[..]);
+            }
+            BackendMessage::AuthenticationSASL { mechanisms } => {
+                buf.put_u32_be(10);
+                for mechanism in mechanisms {
+                    buf.put_string(mechanism);
+                }
+                buf.put(b'\0');
+            }
+            BackendMessage::AuthenticationSASLContinue(data) => {
+                buf.put_u32_be(11);
+                buf.put(&data[..]);
+            }
+            BackendMessage::AuthenticationSASLFinal(data) => {
+                buf.put_u32_be(12);
+                buf.put(&data[..]);
+            }
             BackendMessage::RowDescription(fields) => {
                 buf.put_u16_be(fields.len() as u16);
                 for f in &fields {
@@ -115,29 +175,13 @@ impl Encoder for Codec {
                     buf.put_u16_be(f.format as u16);
                 }
             }
-            BackendMessage::DataRow(fields) => {
+            BackendMessage::DataRow(fields, formats) => {
                 buf.put_u16_be(fields.len() as u16);
-                for f in fields {
+                for (i, f) in fields.into_iter().enumerate() {
                     if let Some(f) = f {
-                        let s: Cow<[u8]> = match f {
-                            FieldValue::Bool(false) => b"f"[..].into(),
-                            FieldValue::Bool(true) => b"t"[..].into(),
-                            FieldValue::Bytea(b) => b.into(),
-                            FieldValue::Date(d) => d.to_string().into_bytes().into(),
-                            FieldValue::Timestamp(ts) => ts.to_string().into_bytes().into(),
-                            FieldValue::Interval(i) => match i {
-                                repr::Interval::Months(count) => format!("{} months", count).into_bytes().into(),
-                                repr::Interval::Duration { is_positive, duration } => format!(
-                                    "{}{:?}",
-                                    if is_positive { "" } else {"-"},
-                                    duration) .into_bytes().into(),
-                            },
-                            FieldValue::Int4(i) => format!("{}", i).into_bytes().into(),
-                            FieldValue::Int8(i) => format!("{}", i).into_bytes().into(),
-                            FieldValue::Float4(f) => format!("{}", f).into_bytes().into(),
-                            FieldValue::Float8(f) => format!("{}", f).into_bytes().into(),
-                            FieldValue::Numeric(n) => format!("{}", n).into_bytes().into(),
-                            FieldValue::Text(ref s) => s.as_bytes().into(),
+                        let s: Cow<[u8]> = match result_format(&formats, i)? {
+                            FieldFormat::Text => encode_value_text(&f),
+                            FieldFormat::Binary => encode_value_binary(&f)?,
                         };
                         buf.put_u32_be(s.len() as u32);
                         buf.put(&*s);
@@ -152,6 +196,16 @@ impl Encoder for Codec {
             BackendMessage::ParseComplete => {
                 eprintln!("placing parse complete");
             }
+            BackendMessage::BindComplete => (),
+            BackendMessage::CloseComplete => (),
+            BackendMessage::ParameterDescription(param_types) => {
+                buf.put_i16_be(param_types.len() as i16);
+                for oid in param_types {
+                    buf.put_u32_be(oid);
+                }
+            }
+            BackendMessage::NoData => (),
+            BackendMessage::PortalSuspended => (),
             BackendMessage::EmptyQueryResponse => (),
             BackendMessage::ReadyForQuery => {
                 buf.put(b'I'); // transaction indicator
@@ -169,7 +223,7 @@ impl Encoder for Codec {
                 buf.put(b'S');
                 buf.put_string(severity.string());
                 buf.put(b'C');
-                buf.put_string(code);
+                buf.put_string(code.code());
                 buf.put(b'M');
                 buf.put_string(message);
                 if let Some(ref detail) = detail {
@@ -194,24 +248,64 @@ enum DecodeState {
     Startup,
     Head,
     Data(u8, usize),
+    /// Streaming a `CopyData` frame whose declared length exceeds the
+    /// configured `max_frame_size`: rather than buffering the whole frame,
+    /// each `decode` call yields whatever bytes have arrived so far as a
+    /// `CopyData` chunk. The `usize` counts the frame's bytes not yet
+    /// yielded; once it reaches zero, decoding returns to `Head`.
+    CopyDataStream(usize),
 }
 
-const MAX_FRAME_SIZE: usize = 8 << 10;
+/// The special "protocol version" a client sends in place of a real version
+/// number to request that the connection be upgraded to TLS before the real
+/// startup packet is sent.
+const VERSION_SSL: u32 = 80_877_103;
+/// The special "protocol version" a client sends to ask the server to
+/// cancel a query running on another connection.
+const VERSION_CANCEL: u32 = 80_877_102;
+
+/// The one-byte reply to a [`FrontendMessage::SslRequest`], sent directly on
+/// the wire rather than through a `Codec`, since it precedes any framed
+/// protocol traffic. `'S'` tells the client to begin a TLS handshake on the
+/// same socket; once that handshake completes, a fresh `Codec` resumes
+/// decoding startup packets on the encrypted stream, mirroring how
+/// rust-postgres wraps the socket after SSL acceptance. `'N'` tells the
+/// client that SSL is not available and the connection continues in the
+/// clear.
+pub fn ssl_response(accept: bool) -> u8 {
+    if accept {
+        b'S'
+    } else {
+        b'N'
+    }
+}
 
-fn parse_frame_len(src: &[u8]) -> Result<usize, io::Error> {
+/// Parses and validates a frame's declared length. `CopyData` frames are
+/// allowed up to `ABSOLUTE_MAX_FRAME_SIZE`, since they're streamed
+/// incrementally rather than buffered whole; every other message type,
+/// including the as-yet-untyped startup packet (`msg_type` `0`), is held to
+/// the tighter, configurable `max_frame_size`.
+fn parse_frame_len(src: &[u8], max_frame_size: usize, msg_type: u8) -> Result<usize, io::Error> {
     let n = cast::usize(NetworkEndian::read_u32(src));
-    if n > MAX_FRAME_SIZE {
-        return Err(io::Error::new(
-            io::ErrorKind::InvalidData,
-            netio::FrameTooBig,
-        ));
-    } else if n < 4 {
+    if n < 4 {
         return Err(io::Error::new(
             io::ErrorKind::InvalidInput,
             "invalid frame length",
         ));
     }
-    Ok(n - 4)
+    let n = n - 4;
+    let ceiling = if msg_type == b'd' {
+        ABSOLUTE_MAX_FRAME_SIZE
+    } else {
+        max_frame_size
+    };
+    if n > ceiling {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            netio::FrameTooBig,
+        ));
+    }
+    Ok(n)
 }
 
 impl Decoder for Codec {
@@ -225,7 +319,7 @@ impl Decoder for Codec {
                     if src.len() < 4 {
                         return Ok(None);
                     }
-                    let frame_len = parse_frame_len(&src)?;
+                    let frame_len = parse_frame_len(&src, self.max_frame_size, 0)?;
                     src.advance(4);
                     src.reserve(frame_len);
                     self.decode_state = DecodeState::Data(b's', frame_len);
@@ -236,10 +330,30 @@ impl Decoder for Codec {
                         return Ok(None);
                     }
                     let msg_type = src[0];
-                    let frame_len = parse_frame_len(&src[1..])?;
+                    let frame_len = parse_frame_len(&src[1..], self.max_frame_size, msg_type)?;
                     src.advance(5);
-                    src.reserve(frame_len);
-                    self.decode_state = DecodeState::Data(msg_type, frame_len);
+                    if msg_type == b'd' && frame_len > self.max_frame_size {
+                        // Don't buffer the whole frame up front; stream it
+                        // out in `CopyDataStream` chunks as bytes arrive.
+                        self.decode_state = DecodeState::CopyDataStream(frame_len);
+                    } else {
+                        src.reserve(frame_len);
+                        self.decode_state = DecodeState::Data(msg_type, frame_len);
+                    }
+                }
+
+                DecodeState::CopyDataStream(remaining) => {
+                    if src.is_empty() {
+                        return Ok(None);
+                    }
+                    let n = cmp::min(remaining, src.len());
+                    let chunk = src.split_to(n);
+                    self.decode_state = if remaining == n {
+                        DecodeState::Head
+                    } else {
+                        DecodeState::CopyDataStream(remaining - n)
+                    };
+                    return Ok(Some(FrontendMessage::CopyData(chunk.to_vec())));
                 }
 
                 DecodeState::Data(msg_type, frame_len) => {
@@ -249,8 +363,29 @@ impl Decoder for Codec {
                     let buf = src.take().freeze();
                     let msg = match msg_type {
                         b's' => {
+                            if buf.len() < 4 {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "invalid startup packet: missing version",
+                                ));
+                            }
                             let version = NetworkEndian::read_u32(&buf[..4]);
-                            FrontendMessage::Startup { version }
+                            match version {
+                                VERSION_SSL => FrontendMessage::SslRequest,
+                                VERSION_CANCEL => {
+                                    if buf.len() < 12 {
+                                        return Err(io::Error::new(
+                                            io::ErrorKind::InvalidData,
+                                            "invalid cancel request: missing conn_id/secret_key",
+                                        ));
+                                    }
+                                    FrontendMessage::CancelRequest {
+                                        conn_id: NetworkEndian::read_u32(&buf[4..8]),
+                                        secret_key: NetworkEndian::read_u32(&buf[8..12]),
+                                    }
+                                }
+                                version => FrontendMessage::Startup { version },
+                            }
                         }
                         b'Q' => FrontendMessage::Query {
                             query: buf.slice_to(frame_len - 1),
@@ -294,6 +429,99 @@ impl Decoder for Codec {
                                 parameter_data_types: param_dts,
                             }
                         }
+                        b'B' => {
+                            let (portal_name, rest) = read_cstr(&buf, frame_len)?;
+                            let (statement_name, rest) = read_cstr(rest, frame_len)?;
+
+                            let (parameter_formats, rest) = read_i16_array(rest)?;
+                            let (parameter_values, rest) = read_value_array(rest)?;
+                            let (result_formats, _rest) = read_i16_array(rest)?;
+
+                            FrontendMessage::Bind {
+                                portal_name: portal_name.into(),
+                                statement_name: statement_name.into(),
+                                parameter_formats: parameter_formats
+                                    .into_iter()
+                                    .map(FieldFormat::from)
+                                    .collect(),
+                                parameter_values,
+                                result_formats: result_formats
+                                    .into_iter()
+                                    .map(FieldFormat::from)
+                                    .collect(),
+                            }
+                        }
+                        b'D' => {
+                            if buf.is_empty() {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "invalid describe message: missing object type",
+                                ));
+                            }
+                            let variant = match buf[0] {
+                                b'S' => DescribeObjectType::Statement,
+                                b'P' => DescribeObjectType::Portal,
+                                other => {
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        format!("invalid describe type {}", other),
+                                    ));
+                                }
+                            };
+                            let (name, _rest) = read_cstr(&buf[1..], frame_len - 1)?;
+                            FrontendMessage::Describe {
+                                variant,
+                                name: name.into(),
+                            }
+                        }
+                        b'E' => {
+                            let (portal_name, rest) = read_cstr(&buf, frame_len)?;
+                            if rest.len() < 4 {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "invalid execute message: missing max row count",
+                                ));
+                            }
+                            let max_rows = NetworkEndian::read_i32(&rest[..4]);
+                            FrontendMessage::Execute {
+                                portal_name: portal_name.into(),
+                                max_rows,
+                            }
+                        }
+                        b'p' => FrontendMessage::Password {
+                            password: buf[..].to_vec(),
+                        },
+                        b'd' => FrontendMessage::CopyData(buf[..].to_vec()),
+                        b'c' => FrontendMessage::CopyDone,
+                        b'f' => {
+                            let (message, _rest) = read_cstr(&buf, frame_len)?;
+                            FrontendMessage::CopyFail(message.into())
+                        }
+                        b'H' => FrontendMessage::Flush,
+                        b'S' => FrontendMessage::Sync,
+                        b'C' => {
+                            if buf.is_empty() {
+                                return Err(io::Error::new(
+                                    io::ErrorKind::InvalidData,
+                                    "invalid close message: missing object type",
+                                ));
+                            }
+                            let variant = match buf[0] {
+                                b'S' => DescribeObjectType::Statement,
+                                b'P' => DescribeObjectType::Portal,
+                                other => {
+                                    return Err(io::Error::new(
+                                        io::ErrorKind::InvalidData,
+                                        format!("invalid close type {}", other),
+                                    ));
+                                }
+                            };
+                            let (name, _rest) = read_cstr(&buf[1..], frame_len - 1)?;
+                            FrontendMessage::Close {
+                                variant,
+                                name: name.into(),
+                            }
+                        }
                         _ => {
                             return Err(io::Error::new(
                                 io::ErrorKind::InvalidData,
@@ -302,7 +530,17 @@ impl Decoder for Codec {
                         }
                     };
                     src.reserve(5);
-                    self.decode_state = DecodeState::Head;
+                    self.decode_state = match &msg {
+                        // A declined `SslRequest` leaves the client sending
+                        // its real `Startup` on this same connection, in the
+                        // same untyped `[length][version]` format as the
+                        // packet we just decoded -- never a typed message.
+                        // (An accepted request is moot here: per
+                        // `ssl_response`'s docs, a fresh `Codec` takes over
+                        // once the TLS handshake completes.)
+                        FrontendMessage::SslRequest => DecodeState::Startup,
+                        _ => DecodeState::Head,
+                    };
                     return Ok(Some(msg));
                 }
             }
@@ -331,6 +569,180 @@ impl std::fmt::Display for MyErr {
     }
 }
 
+/// Reads a `[i16 count][i16; count]` array, as used for format codes in the
+/// `Bind` message.
+fn read_i16_array(buf: &[u8]) -> Result<(Vec<i16>, &[u8]), io::Error> {
+    if buf.len() < 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+    }
+    let n = NetworkEndian::read_i16(&buf[..2]);
+    if n < 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+    }
+    let mut buf = &buf[2..];
+    let mut out = Vec::with_capacity(n as usize);
+    for _ in 0..n {
+        if buf.len() < 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+        }
+        out.push(NetworkEndian::read_i16(&buf[..2]));
+        buf = &buf[2..];
+    }
+    Ok((out, buf))
+}
+
+/// Reads a `[i16 count][i32 len; bytes]` array of parameter values, as used
+/// for the parameter values in the `Bind` message. A length of `-1`
+/// indicates a NULL parameter.
+fn read_value_array(buf: &[u8]) -> Result<(Vec<Option<Vec<u8>>>, &[u8]), io::Error> {
+    if buf.len() < 2 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+    }
+    let n = NetworkEndian::read_i16(&buf[..2]);
+    if n < 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+    }
+    let mut buf = &buf[2..];
+    let mut out = Vec::with_capacity(n as usize);
+    for _ in 0..n {
+        if buf.len() < 4 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+        }
+        let len = NetworkEndian::read_i32(&buf[..4]);
+        buf = &buf[4..];
+        if len < 0 {
+            out.push(None);
+        } else {
+            let len = len as usize;
+            if buf.len() < len {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, MyErr));
+            }
+            out.push(Some(buf[..len].to_vec()));
+            buf = &buf[len..];
+        }
+    }
+    Ok((out, buf))
+}
+
+/// Writes the body shared by `CopyOutResponse`, `CopyInResponse`, and
+/// `CopyBothResponse`: an overall format code, the column count, and a
+/// per-column format code. The overall format is binary only when every
+/// column is; psql and other clients otherwise assume text.
+fn encode_copy_formats(buf: &mut Vec<u8>, column_formats: &[FieldFormat]) {
+    let overall = if !column_formats.is_empty()
+        && column_formats.iter().all(|f| *f == FieldFormat::Binary)
+    {
+        1
+    } else {
+        0
+    };
+    buf.put_u8(overall);
+    buf.put_i16_be(column_formats.len() as i16);
+    for format in column_formats {
+        buf.put_i16_be(*format as i16);
+    }
+}
+
+/// Looks up the format negotiated for result column `i`, per the rule in the
+/// `Bind` message: an empty list means text for every column, a one-element
+/// list applies to every column, otherwise there's one entry per column. A
+/// client's `Bind` is responsible for supplying one entry per column in that
+/// last case; since the column count isn't known until `DataRow` encoding,
+/// a list shorter than the row is only catchable here, so asking for a
+/// column past its end is reported as an error rather than indexed
+/// blindly. Entries beyond the row's last column are ignored.
+fn result_format(formats: &[FieldFormat], i: usize) -> Result<FieldFormat, io::Error> {
+    match formats {
+        [] => Ok(FieldFormat::Text),
+        [f] => Ok(*f),
+        fs => fs.get(i).copied().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Bind specified {} result format(s), but the portal has at least {} columns",
+                    fs.len(),
+                    i + 1
+                ),
+            )
+        }),
+    }
+}
+
+/// The Postgres epoch, used as the zero point for binary `date` and
+/// `timestamp` values.
+fn pg_epoch() -> NaiveDate {
+    NaiveDate::from_ymd(2000, 1, 1)
+}
+
+fn encode_value_text(val: &FieldValue) -> Cow<[u8]> {
+    match val {
+        FieldValue::Bool(false) => b"f"[..].into(),
+        FieldValue::Bool(true) => b"t"[..].into(),
+        FieldValue::Bytea(b) => b.clone().into(),
+        FieldValue::Date(d) => d.to_string().into_bytes().into(),
+        FieldValue::Timestamp(ts) => ts.to_string().into_bytes().into(),
+        FieldValue::Interval(i) => match i {
+            repr::Interval::Months(count) => format!("{} months", count).into_bytes().into(),
+            repr::Interval::Duration { is_positive, duration } => format!(
+                "{}{:?}",
+                if *is_positive { "" } else { "-" },
+                duration
+            )
+            .into_bytes()
+            .into(),
+        },
+        FieldValue::Int4(i) => format!("{}", i).into_bytes().into(),
+        FieldValue::Int8(i) => format!("{}", i).into_bytes().into(),
+        FieldValue::Float4(f) => format!("{}", f).into_bytes().into(),
+        FieldValue::Float8(f) => format!("{}", f).into_bytes().into(),
+        FieldValue::Numeric(n) => format!("{}", n).into_bytes().into(),
+        FieldValue::Text(s) => s.as_bytes().to_vec().into(),
+    }
+}
+
+/// Encodes a value using the type-specific binary representations described
+/// in the "[Binary Data Types][1]" section of the PostgreSQL protocol docs.
+/// `Numeric` and `Interval` don't yet have a binary encoder; since a client
+/// that asked for binary format will feed whatever bytes we send straight to
+/// a binary parser, we refuse those two rather than quietly handing back
+/// text that would be misread as binary.
+///
+/// [1]: https://www.postgresql.org/docs/11/protocol-message-formats.html
+fn encode_value_binary(val: &FieldValue) -> Result<Cow<[u8]>, io::Error> {
+    let encoded = match val {
+        FieldValue::Bool(b) => vec![if *b { 1 } else { 0 }].into(),
+        FieldValue::Bytea(b) => b.clone().into(),
+        FieldValue::Int4(i) => i.to_be_bytes().to_vec().into(),
+        FieldValue::Int8(i) => i.to_be_bytes().to_vec().into(),
+        FieldValue::Float4(f) => f.to_be_bytes().to_vec().into(),
+        FieldValue::Float8(f) => f.to_be_bytes().to_vec().into(),
+        FieldValue::Date(d) => {
+            let days = d.signed_duration_since(pg_epoch()).num_days() as i32;
+            days.to_be_bytes().to_vec().into()
+        }
+        FieldValue::Timestamp(ts) => {
+            let micros = ts
+                .signed_duration_since(pg_epoch().and_hms(0, 0, 0))
+                .num_microseconds()
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("timestamp {} overflows the binary representation", ts),
+                    )
+                })?;
+            micros.to_be_bytes().to_vec().into()
+        }
+        FieldValue::Text(s) => s.as_bytes().to_vec().into(),
+        FieldValue::Numeric(_) | FieldValue::Interval(_) => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("binary format is not supported for {:?}", val),
+            ));
+        }
+    };
+    Ok(encoded)
+}
+
 fn read_cstr(slice: &[u8], max: usize) -> Result<(&str, &[u8]), io::Error> {
     fn err(source: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
         io::Error::new(io::ErrorKind::InvalidInput, source.into())
@@ -347,3 +759,318 @@ fn read_cstr(slice: &[u8], max: usize) -> Result<(&str, &[u8]), io::Error> {
         Err(err(MyErr))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn encode_value_binary_ints() {
+        assert_eq!(
+            &*encode_value_binary(&FieldValue::Int4(-1)).unwrap(),
+            &(-1_i32).to_be_bytes()
+        );
+        assert_eq!(
+            &*encode_value_binary(&FieldValue::Int8(1_234_567_890_123)).unwrap(),
+            &1_234_567_890_123_i64.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn encode_value_binary_date_is_days_since_pg_epoch() {
+        let d = NaiveDate::from_ymd(2000, 1, 2);
+        assert_eq!(
+            &*encode_value_binary(&FieldValue::Date(d)).unwrap(),
+            &1_i32.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn encode_value_binary_timestamp_is_micros_since_pg_epoch() {
+        let ts = pg_epoch().and_hms(0, 0, 1);
+        assert_eq!(
+            &*encode_value_binary(&FieldValue::Timestamp(ts)).unwrap(),
+            &1_000_000_i64.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn encode_value_binary_rejects_numeric_and_interval() {
+        assert!(encode_value_binary(&FieldValue::Numeric(1.5)).is_err());
+        assert!(encode_value_binary(&FieldValue::Interval(repr::Interval::Months(3))).is_err());
+    }
+
+    #[test]
+    fn result_format_empty_list_is_always_text() {
+        assert_eq!(result_format(&[], 0).unwrap(), FieldFormat::Text);
+        assert_eq!(result_format(&[], 41).unwrap(), FieldFormat::Text);
+    }
+
+    #[test]
+    fn result_format_single_entry_applies_to_every_column() {
+        assert_eq!(
+            result_format(&[FieldFormat::Binary], 0).unwrap(),
+            FieldFormat::Binary
+        );
+        assert_eq!(
+            result_format(&[FieldFormat::Binary], 9).unwrap(),
+            FieldFormat::Binary
+        );
+    }
+
+    #[test]
+    fn result_format_rejects_mismatched_column_count() {
+        let formats = [FieldFormat::Text, FieldFormat::Binary];
+        assert_eq!(result_format(&formats, 0).unwrap(), FieldFormat::Text);
+        assert_eq!(result_format(&formats, 1).unwrap(), FieldFormat::Binary);
+        assert!(result_format(&formats, 2).is_err());
+    }
+
+    /// Builds a typed frame (`[msg_type][len][body]`), as expected by a
+    /// `Codec` already in `DecodeState::Head`.
+    fn typed_frame(msg_type: u8, body: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u8(msg_type);
+        buf.put_u32_be((body.len() + 4) as u32);
+        buf.put(body);
+        buf
+    }
+
+    /// Builds an untyped frame (`[len][body]`), as expected by a `Codec` in
+    /// `DecodeState::Startup` (the real `Startup`/`SslRequest`/`CancelRequest`
+    /// packets that precede any typed message).
+    fn untyped_frame(body: &[u8]) -> BytesMut {
+        let mut buf = BytesMut::new();
+        buf.put_u32_be((body.len() + 4) as u32);
+        buf.put(body);
+        buf
+    }
+
+    /// A `Codec` already past the startup dance, ready to decode typed
+    /// messages.
+    fn head_codec() -> Codec {
+        Codec {
+            decode_state: DecodeState::Head,
+            max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+        }
+    }
+
+    #[test]
+    fn bind_decodes_portal_statement_and_formats() {
+        let mut body = vec![];
+        body.extend_from_slice(b"\0"); // portal_name: unnamed
+        body.extend_from_slice(b"s1\0"); // statement_name
+        body.extend_from_slice(&0_i16.to_be_bytes()); // parameter_formats: none
+        body.extend_from_slice(&1_i16.to_be_bytes()); // parameter_values: one
+        body.extend_from_slice(&3_i32.to_be_bytes());
+        body.extend_from_slice(b"abc");
+        body.extend_from_slice(&1_i16.to_be_bytes()); // result_formats: one
+        body.extend_from_slice(&1_i16.to_be_bytes()); // Binary
+
+        let mut buf = typed_frame(b'B', &body);
+        let msg = head_codec().decode(&mut buf).unwrap().unwrap();
+        match msg {
+            FrontendMessage::Bind {
+                portal_name,
+                statement_name,
+                parameter_formats,
+                parameter_values,
+                result_formats,
+            } => {
+                assert_eq!(portal_name, "");
+                assert_eq!(statement_name, "s1");
+                assert_eq!(parameter_formats, vec![]);
+                assert_eq!(parameter_values, vec![Some(b"abc".to_vec())]);
+                assert_eq!(result_formats, vec![FieldFormat::Binary]);
+            }
+            other => panic!("expected Bind, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn bind_rejects_truncated_frame() {
+        // Just the two (empty) cstrs; missing the format/value arrays
+        // entirely.
+        let body = b"\0\0";
+        let mut buf = typed_frame(b'B', body);
+        assert!(head_codec().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn describe_decodes_statement_and_portal() {
+        let mut buf = typed_frame(b'D', b"Sstmt1\0");
+        let msg = head_codec().decode(&mut buf).unwrap().unwrap();
+        match msg {
+            FrontendMessage::Describe { variant, name } => {
+                assert_eq!(variant, DescribeObjectType::Statement);
+                assert_eq!(name, "stmt1");
+            }
+            other => panic!("expected Describe, got {:?}", other),
+        }
+
+        let mut buf = typed_frame(b'D', b"Pportal1\0");
+        let msg = head_codec().decode(&mut buf).unwrap().unwrap();
+        match msg {
+            FrontendMessage::Describe { variant, name } => {
+                assert_eq!(variant, DescribeObjectType::Portal);
+                assert_eq!(name, "portal1");
+            }
+            other => panic!("expected Describe, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn describe_rejects_empty_frame() {
+        let mut buf = typed_frame(b'D', b"");
+        assert!(head_codec().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn execute_decodes_portal_and_max_rows() {
+        let mut body = b"portal1\0".to_vec();
+        body.extend_from_slice(&42_i32.to_be_bytes());
+        let mut buf = typed_frame(b'E', &body);
+        let msg = head_codec().decode(&mut buf).unwrap().unwrap();
+        match msg {
+            FrontendMessage::Execute {
+                portal_name,
+                max_rows,
+            } => {
+                assert_eq!(portal_name, "portal1");
+                assert_eq!(max_rows, 42);
+            }
+            other => panic!("expected Execute, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn execute_rejects_missing_max_rows() {
+        let mut buf = typed_frame(b'E', b"portal1\0");
+        assert!(head_codec().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn close_decodes_statement_and_rejects_empty_frame() {
+        let mut buf = typed_frame(b'C', b"Sstmt1\0");
+        let msg = head_codec().decode(&mut buf).unwrap().unwrap();
+        match msg {
+            FrontendMessage::Close { variant, name } => {
+                assert_eq!(variant, DescribeObjectType::Statement);
+                assert_eq!(name, "stmt1");
+            }
+            other => panic!("expected Close, got {:?}", other),
+        }
+
+        let mut buf = typed_frame(b'C', b"");
+        assert!(head_codec().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn flush_and_sync_decode_with_empty_bodies() {
+        let mut buf = typed_frame(b'H', b"");
+        match head_codec().decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::Flush => (),
+            other => panic!("expected Flush, got {:?}", other),
+        }
+
+        let mut buf = typed_frame(b'S', b"");
+        match head_codec().decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::Sync => (),
+            other => panic!("expected Sync, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sslrequest_returns_decoder_to_startup_state() {
+        let mut codec = Codec::new();
+        let mut buf = untyped_frame(&VERSION_SSL.to_be_bytes());
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::SslRequest => (),
+            other => panic!("expected SslRequest, got {:?}", other),
+        }
+
+        // A declined SslRequest is followed by a real Startup packet, still
+        // in the untyped `[len][version]` format, not a typed message.
+        let mut buf = untyped_frame(&196_608_u32.to_be_bytes()); // protocol 3.0
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::Startup { version } => assert_eq!(version, 196_608),
+            other => panic!("expected Startup, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cancelrequest_decodes_conn_id_and_secret_key() {
+        let mut body = VERSION_CANCEL.to_be_bytes().to_vec();
+        body.extend_from_slice(&123_u32.to_be_bytes());
+        body.extend_from_slice(&456_u32.to_be_bytes());
+        let mut buf = untyped_frame(&body);
+        match Codec::new().decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::CancelRequest {
+                conn_id,
+                secret_key,
+            } => {
+                assert_eq!(conn_id, 123);
+                assert_eq!(secret_key, 456);
+            }
+            other => panic!("expected CancelRequest, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn cancelrequest_rejects_truncated_frame() {
+        let body = VERSION_CANCEL.to_be_bytes().to_vec();
+        let mut buf = untyped_frame(&body);
+        assert!(Codec::new().decode(&mut buf).is_err());
+    }
+
+    #[test]
+    fn copydata_decodes_whole_frame_when_under_max_size() {
+        let mut buf = typed_frame(b'd', b"hello");
+        match head_codec().decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::CopyData(data) => assert_eq!(data, b"hello".to_vec()),
+            other => panic!("expected CopyData, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn copydone_and_copyfail_decode() {
+        let mut buf = typed_frame(b'c', b"");
+        match head_codec().decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::CopyDone => (),
+            other => panic!("expected CopyDone, got {:?}", other),
+        }
+
+        let mut buf = typed_frame(b'f', b"out of disk space\0");
+        match head_codec().decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::CopyFail(reason) => assert_eq!(reason, "out of disk space"),
+            other => panic!("expected CopyFail, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn copydatastream_splits_an_oversized_frame_across_decode_calls() {
+        let mut codec = Codec {
+            decode_state: DecodeState::Head,
+            max_frame_size: 4,
+        };
+        let body = b"0123456789";
+
+        // Header plus only the first half of the body has arrived so far.
+        let mut buf = BytesMut::new();
+        buf.put_u8(b'd');
+        buf.put_u32_be((body.len() + 4) as u32);
+        buf.put(&body[..5]);
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::CopyData(chunk) => assert_eq!(chunk, body[..5].to_vec()),
+            other => panic!("expected CopyData, got {:?}", other),
+        }
+
+        // The rest of the body now arrives.
+        buf.put(&body[5..]);
+        match codec.decode(&mut buf).unwrap().unwrap() {
+            FrontendMessage::CopyData(chunk) => assert_eq!(chunk, body[5..].to_vec()),
+            other => panic!("expected CopyData, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,28 @@
+// Copyright 2019 Materialize, Inc. All rights reserved.
+//
+// This file is part of Materialize. Materialize may not be used or
+// distributed without the express permission of Materialize, Inc.
+
+//! Typed SQLSTATE error codes, as assigned by the "[Appendix A. PostgreSQL
+//! Error Codes][1]" table in the PostgreSQL documentation.
+//!
+//! `SqlState` and its `code`/`from_code` methods are generated at build time
+//! by `build.rs` from `pgwire/sqlstates.csv`, the way rust-postgres
+//! generates its own `SqlState` from `errcodes.txt`. Add a line to that CSV
+//! when a new error needs a typed code; there's no need to touch this file.
+//!
+//! [1]: https://www.postgresql.org/docs/11/errcodes-appendix.html
+
+include!(concat!(env!("OUT_DIR"), "/sqlstate.rs"));
+
+impl SqlState {
+    /// True for the "undefined table" (`42P01`) and "undefined object"
+    /// (`42704`) classes Postgres uses for a `DROP` of a nonexistent
+    /// relation.
+    pub fn is_undefined(&self) -> bool {
+        match self {
+            SqlState::UndefinedTable | SqlState::UndefinedObject => true,
+            _ => false,
+        }
+    }
+}
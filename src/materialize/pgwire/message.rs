@@ -0,0 +1,268 @@
+// Copyright 2019 Materialize, Inc. All rights reserved.
+//
+// This file is part of Materialize. Materialize may not be used or
+// distributed without the express permission of Materialize, Inc.
+
+//! Definitions of the frontend (client-to-server) and backend
+//! (server-to-client) messages that make up the pgwire protocol, along with
+//! the scalar types used in a [`BackendMessage::DataRow`].
+//!
+//! See the [`Codec`](crate::pgwire::Codec) docs for the module that turns
+//! these types into and out of bytes on the wire.
+
+use bytes::Bytes;
+
+use crate::pgwire::codes::SqlState;
+
+/// A message from a pgwire client.
+#[derive(Debug)]
+pub enum FrontendMessage {
+    /// Begin a connection.
+    Startup { version: u32 },
+
+    /// Request that the connection be upgraded to use TLS, per the "[SSL
+    /// Session Encryption][1]" negotiation that precedes a normal
+    /// `Startup`.
+    ///
+    /// [1]: https://www.postgresql.org/docs/11/protocol-flow.html#id-1.10.5.7.11
+    SslRequest,
+
+    /// Ask the server to cancel the query running on another connection.
+    /// Sent over a fresh connection, never the one being cancelled.
+    CancelRequest {
+        /// The process ID of the connection to cancel, as reported by that
+        /// connection's `BackendKeyData`.
+        conn_id: u32,
+        /// The secret key for that connection, as reported by that
+        /// connection's `BackendKeyData`.
+        secret_key: u32,
+    },
+
+    /// Execute the specified SQL statement.
+    Query {
+        /// The SQL to execute.
+        query: Bytes,
+    },
+
+    /// Parse the specified SQL into a prepared statement.
+    Parse {
+        /// The name of the prepared statement to create. An empty string
+        /// specifies the unnamed prepared statement.
+        name: String,
+        /// The SQL to parse.
+        sql: String,
+        /// The number of explicitly-specified parameter data types.
+        parameter_data_type_count: u16,
+        /// The data types for the parameters in the prepared statement.
+        /// Each element in this list is a PostgreSQL type OID, or zero,
+        /// which indicates the parameter type is unspecified.
+        parameter_data_types: Vec<u32>,
+    },
+
+    /// Bind a prepared statement to a portal, supplying parameter values.
+    Bind {
+        /// The destination portal. An empty string selects the unnamed
+        /// portal.
+        portal_name: String,
+        /// The source prepared statement. An empty string selects the
+        /// unnamed prepared statement.
+        statement_name: String,
+        /// The format used for each parameter value, or an empty list if
+        /// all parameters use the default (text) format.
+        parameter_formats: Vec<FieldFormat>,
+        /// The value of each parameter, or `None` if the parameter is NULL.
+        parameter_values: Vec<Option<Vec<u8>>>,
+        /// The format the client wants for each result column, or an empty
+        /// list if all columns should use the default (text) format.
+        result_formats: Vec<FieldFormat>,
+    },
+
+    /// Describe an existing prepared statement or portal.
+    Describe {
+        /// The type of object to describe.
+        variant: DescribeObjectType,
+        /// The name of the object to describe.
+        name: String,
+    },
+
+    /// Execute a bound portal.
+    Execute {
+        /// The name of the portal to execute.
+        portal_name: String,
+        /// The maximum number of rows to return before suspending, or `0`
+        /// for no limit.
+        max_rows: i32,
+    },
+
+    /// Flush any pending output, without doing anything else.
+    Flush,
+
+    /// Finish an extended-query message exchange, instructing the server to
+    /// process any messages received since the last `Sync`.
+    Sync,
+
+    /// Close an existing prepared statement or portal.
+    Close {
+        /// The type of object to close.
+        variant: DescribeObjectType,
+        /// The name of the object to close.
+        name: String,
+    },
+
+    /// Terminate a connection.
+    Terminate,
+
+    /// A password, or a SASL initial response/response, sent in answer to
+    /// an authentication request. Interpreting the bytes depends on which
+    /// authentication method the server is running.
+    Password { password: Vec<u8> },
+
+    /// A chunk of data sent as part of a `COPY ... FROM STDIN`.
+    CopyData(Vec<u8>),
+
+    /// The client has finished sending `COPY` data.
+    CopyDone,
+
+    /// The client has aborted a `COPY` operation; the string explains why.
+    CopyFail(String),
+}
+
+/// The wire format used to encode a parameter value or a result column.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FieldFormat {
+    /// The human-readable, variable-length format expected by most clients.
+    Text = 0,
+    /// A type-specific binary representation.
+    Binary = 1,
+}
+
+impl From<i16> for FieldFormat {
+    fn from(code: i16) -> FieldFormat {
+        match code {
+            1 => FieldFormat::Binary,
+            _ => FieldFormat::Text,
+        }
+    }
+}
+
+/// The kind of object named in a [`FrontendMessage::Describe`] or
+/// [`FrontendMessage::Close`] message.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DescribeObjectType {
+    /// A prepared statement.
+    Statement,
+    /// A portal.
+    Portal,
+}
+
+/// A message from the server to a pgwire client.
+#[derive(Debug)]
+pub enum BackendMessage {
+    AuthenticationOk,
+    /// Instructs the client to send a cleartext `Password` message.
+    AuthenticationCleartextPassword,
+    /// Instructs the client to send a `Password` message containing the MD5
+    /// hash of the password salted with `salt`.
+    AuthenticationMD5Password { salt: [u8; 4] },
+    /// Instructs the client to begin a SASL handshake using one of the
+    /// listed mechanisms.
+    AuthenticationSASL { mechanisms: Vec<&'static str> },
+    /// The SASL "server-first-message".
+    AuthenticationSASLContinue(Vec<u8>),
+    /// The SASL "server-final-message".
+    AuthenticationSASLFinal(Vec<u8>),
+    RowDescription(Vec<FieldDescription>),
+    /// A single row of the result, along with the formats in which each of
+    /// its fields should be encoded. An empty `formats` list means every
+    /// field uses the default (text) format; a one-element list means that
+    /// format applies to every field; otherwise there is one entry per
+    /// field.
+    DataRow(Vec<Option<FieldValue>>, Vec<FieldFormat>),
+    CommandComplete { tag: String },
+    EmptyQueryResponse,
+    ReadyForQuery,
+    ParameterStatus(String, String),
+    ParseComplete,
+    BindComplete,
+    CloseComplete,
+    /// The types of the parameters of the described prepared statement.
+    ParameterDescription(Vec<u32>),
+    /// Sent in response to a `Describe` of a portal that returns no rows.
+    NoData,
+    /// Sent after an `Execute` that stops before exhausting the portal
+    /// because it hit the requested row limit.
+    PortalSuspended,
+    ErrorResponse {
+        severity: Severity,
+        code: SqlState,
+        message: String,
+        detail: Option<String>,
+    },
+    /// Sent before streaming the results of a `COPY ... TO STDOUT`.
+    CopyOutResponse { column_formats: Vec<FieldFormat> },
+    /// Sent to invite the client to begin a `COPY ... FROM STDIN`.
+    CopyInResponse { column_formats: Vec<FieldFormat> },
+    /// Sent to invite the client into a bidirectional `COPY`, as used by
+    /// logical replication.
+    CopyBothResponse { column_formats: Vec<FieldFormat> },
+    /// A chunk of data sent as part of a `COPY ... TO STDOUT`.
+    CopyData(Vec<u8>),
+    /// Sent after the last chunk of a `COPY ... TO STDOUT`.
+    CopyDone,
+}
+
+/// A description of a single column in a [`BackendMessage::RowDescription`].
+#[derive(Debug)]
+pub struct FieldDescription {
+    pub name: String,
+    pub table_id: u32,
+    pub column_id: u16,
+    pub type_oid: u32,
+    pub type_len: i16,
+    pub type_mod: i32,
+    pub format: FieldFormat,
+}
+
+/// A scalar value that can appear in a [`BackendMessage::DataRow`].
+#[derive(Debug)]
+pub enum FieldValue {
+    Bool(bool),
+    Bytea(Vec<u8>),
+    Date(chrono::NaiveDate),
+    Timestamp(chrono::NaiveDateTime),
+    Interval(repr::Interval),
+    Int4(i32),
+    Int8(i64),
+    Float4(f32),
+    Float8(f64),
+    Numeric(f64),
+    Text(String),
+}
+
+/// The severity reported in a [`BackendMessage::ErrorResponse`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Severity {
+    Error,
+    Fatal,
+    Panic,
+    Warning,
+    Notice,
+    Debug,
+    Info,
+    Log,
+}
+
+impl Severity {
+    pub fn string(self) -> &'static str {
+        match self {
+            Severity::Error => "ERROR",
+            Severity::Fatal => "FATAL",
+            Severity::Panic => "PANIC",
+            Severity::Warning => "WARNING",
+            Severity::Notice => "NOTICE",
+            Severity::Debug => "DEBUG",
+            Severity::Info => "INFO",
+            Severity::Log => "LOG",
+        }
+    }
+}
@@ -3,7 +3,7 @@
 // This file is part of Materialize. Materialize may not be used or
 // distributed without the express permission of Materialize, Inc.
 
-use postgres::error::DbError;
+use postgres::error::{DbError, SqlState};
 use sqlparser::dialect::AnsiSqlDialect;
 use sqlparser::sqlast::SQLStatement;
 use sqlparser::sqlparser::Parser as SQLParser;
@@ -84,7 +84,13 @@ impl SqlAction {
                 let err_string = err.to_string();
                 if let Some(err) = err.into_source() {
                     if let Ok(err) = err.downcast::<DbError>() {
-                        if err.message() == "target node does not exist" {
+                        // A DROP of an object that doesn't exist is
+                        // idempotent, so swallow the "undefined table" and
+                        // "undefined object" classes regardless of the
+                        // error's message wording.
+                        if err.code() == &SqlState::UNDEFINED_TABLE
+                            || err.code() == &SqlState::UNDEFINED_OBJECT
+                        {
                             return Ok(());
                         }
                     }